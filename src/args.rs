@@ -1,8 +1,23 @@
 use clap::{Parser, ValueEnum};
+use dither_qr::DitherKernel;
+use image::Rgb;
 use qrcode::EcLevel;
 use std::path::PathBuf;
 
-#[derive(ValueEnum, Clone, Debug)]
+/// Parse a hex color string (with or without a leading `#`) into an RGB triple.
+pub fn parse_hex_color(s: &str) -> Result<Rgb<u8>, String> {
+    let s = s.trim_start_matches('#');
+    if s.len() != 6 {
+        return Err(format!(
+            "expected a 6-digit hex color like `ff8800`, got `{s}`"
+        ));
+    }
+
+    let channel = |range| u8::from_str_radix(&s[range], 16).map_err(|e| e.to_string());
+    Ok(Rgb([channel(0..2)?, channel(2..4)?, channel(4..6)?]))
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
 #[clap(rename_all = "UPPER")]
 pub enum EcArg {
     L,
@@ -22,6 +37,47 @@ impl From<EcArg> for EcLevel {
     }
 }
 
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+#[clap(rename_all = "lower")]
+pub enum FormatArg {
+    /// Infer the format from the output file's extension (default)
+    Auto,
+    /// Rasterize to a PNG/JPEG/... image, per the output extension
+    Raster,
+    /// Emit a scalable SVG vector image
+    Svg,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+#[clap(rename_all = "kebab-case")]
+pub enum DitherArg {
+    /// Classic 4-neighbor Floyd-Steinberg (default)
+    FloydSteinberg,
+    /// Smoother 12-neighbor kernel; trades sharpness for gradient smoothness
+    JarvisJudiceNinke,
+    /// Smoother 12-neighbor kernel, similar to Jarvis-Judice-Ninke
+    Stucki,
+    /// Discards a quarter of the error for punchier contrast
+    Atkinson,
+    /// Ordered dithering against a tiled 4x4 Bayer matrix
+    Bayer4,
+    /// Ordered dithering against a tiled 8x8 Bayer matrix
+    Bayer8,
+}
+
+impl From<DitherArg> for DitherKernel {
+    fn from(v: DitherArg) -> Self {
+        match v {
+            DitherArg::FloydSteinberg => DitherKernel::FloydSteinberg,
+            DitherArg::JarvisJudiceNinke => DitherKernel::JarvisJudiceNinke,
+            DitherArg::Stucki => DitherKernel::Stucki,
+            DitherArg::Atkinson => DitherKernel::Atkinson,
+            DitherArg::Bayer4 => DitherKernel::Bayer4,
+            DitherArg::Bayer8 => DitherKernel::Bayer8,
+        }
+    }
+}
+
 #[derive(Parser)]
 #[command(name = "dithered-qr")]
 #[command(about = "Generate dithered QR codes with image overlay using Floyd-Steinberg dithering")]
@@ -61,4 +117,38 @@ pub struct Args {
     /// Upscale factor for output image (default: 1, no upscaling)
     #[arg(short = 'u', long, default_value = "1")]
     pub upscale: u32,
+
+    /// Verify the rendered output decodes back to `text`, retuning the EC
+    /// level, ratio and dithering strength and retrying if it doesn't
+    #[arg(long)]
+    pub verify: bool,
+
+    /// Maximum retuning attempts when `--verify` is set (default: 8)
+    #[arg(long, default_value = "8")]
+    pub verify_retries: usize,
+
+    /// Output format (default: inferred from the output file extension)
+    #[arg(long, value_enum, default_value = "auto")]
+    pub format: FormatArg,
+
+    /// Size of one grid cell in SVG user units (a QR module spans `ratio`
+    /// cells), used only for SVG output (default: 10)
+    #[arg(long, default_value = "10.0")]
+    pub svg_scale: f32,
+
+    /// Dark (foreground) module color, as a hex string (default: 000000)
+    #[arg(long, value_parser = parse_hex_color, default_value = "000000")]
+    pub dark_color: Rgb<u8>,
+
+    /// Light (background) module color, as a hex string (default: ffffff)
+    #[arg(long, value_parser = parse_hex_color, default_value = "ffffff")]
+    pub light_color: Rgb<u8>,
+
+    /// Quiet-zone margin surrounding the code, in QR modules (default: 4)
+    #[arg(long, default_value = "4")]
+    pub quiet_zone: usize,
+
+    /// Dithering kernel used for the free-cell pass (default: floyd-steinberg)
+    #[arg(long, value_enum, default_value = "floyd-steinberg")]
+    pub dither: DitherArg,
 }
@@ -0,0 +1,25 @@
+use image::Rgb;
+
+/// WCAG relative luminance of an sRGB color.
+fn relative_luminance(c: Rgb<u8>) -> f32 {
+    let to_linear = |channel: u8| {
+        let v = channel as f32 / 255.0;
+        if v <= 0.03928 {
+            v / 12.92
+        } else {
+            ((v + 0.055) / 1.055).powf(2.4)
+        }
+    };
+
+    let [r, g, b] = c.0;
+    0.2126 * to_linear(r) + 0.7152 * to_linear(g) + 0.0722 * to_linear(b)
+}
+
+/// WCAG contrast ratio between two colors, from 1.0 (no contrast) to 21.0
+/// (black on white). Used to warn when a chosen dark/light pair is too close
+/// together for reliable scanning.
+pub fn contrast_ratio(a: Rgb<u8>, b: Rgb<u8>) -> f32 {
+    let (la, lb) = (relative_luminance(a), relative_luminance(b));
+    let (hi, lo) = if la > lb { (la, lb) } else { (lb, la) };
+    (hi + 0.05) / (lo + 0.05)
+}
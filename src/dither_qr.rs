@@ -4,26 +4,134 @@ use image::{ImageBuffer, Pixel, Rgb, RgbImage};
 use ndarray::Array2;
 use rayon::prelude::*;
 
+/// Error-diffusion/ordered-dithering kernel used for the `Free`-cell pass.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DitherKernel {
+    /// Classic 4-neighbor Floyd-Steinberg (the original default).
+    FloydSteinberg,
+    /// Larger two-row kernel spreading error across 12 forward neighbors for smoother gradients.
+    JarvisJudiceNinke,
+    /// Like Jarvis-Judice-Ninke but with different weights; also smooth, slightly sharper.
+    Stucki,
+    /// Distributes only 3/4 of the error across 6 neighbors, deliberately
+    /// discarding the rest for punchier contrast.
+    Atkinson,
+    /// Ordered dithering against a tiled 4x4 Bayer matrix (no diffusion).
+    Bayer4,
+    /// Ordered dithering against a tiled 8x8 Bayer matrix (no diffusion).
+    Bayer8,
+}
+
+/// One neighbor offset and the fraction of the source cell's error it
+/// receives. Fractions need not sum to 1.0 -- [`DitherKernel::Atkinson`]
+/// deliberately sums to 0.75.
+#[derive(Clone, Copy)]
+struct DiffusionStep {
+    dx: i32,
+    dy: i32,
+    weight: f32,
+}
+
+const FLOYD_STEINBERG: [DiffusionStep; 4] = [
+    DiffusionStep { dx: 1, dy: 0, weight: 7.0 / 16.0 },
+    DiffusionStep { dx: -1, dy: 1, weight: 3.0 / 16.0 },
+    DiffusionStep { dx: 0, dy: 1, weight: 5.0 / 16.0 },
+    DiffusionStep { dx: 1, dy: 1, weight: 1.0 / 16.0 },
+];
+
+const JARVIS_JUDICE_NINKE: [DiffusionStep; 12] = [
+    DiffusionStep { dx: 1, dy: 0, weight: 7.0 / 48.0 },
+    DiffusionStep { dx: 2, dy: 0, weight: 5.0 / 48.0 },
+    DiffusionStep { dx: -2, dy: 1, weight: 3.0 / 48.0 },
+    DiffusionStep { dx: -1, dy: 1, weight: 5.0 / 48.0 },
+    DiffusionStep { dx: 0, dy: 1, weight: 7.0 / 48.0 },
+    DiffusionStep { dx: 1, dy: 1, weight: 5.0 / 48.0 },
+    DiffusionStep { dx: 2, dy: 1, weight: 3.0 / 48.0 },
+    DiffusionStep { dx: -2, dy: 2, weight: 1.0 / 48.0 },
+    DiffusionStep { dx: -1, dy: 2, weight: 3.0 / 48.0 },
+    DiffusionStep { dx: 0, dy: 2, weight: 5.0 / 48.0 },
+    DiffusionStep { dx: 1, dy: 2, weight: 3.0 / 48.0 },
+    DiffusionStep { dx: 2, dy: 2, weight: 1.0 / 48.0 },
+];
+
+const STUCKI: [DiffusionStep; 12] = [
+    DiffusionStep { dx: 1, dy: 0, weight: 8.0 / 42.0 },
+    DiffusionStep { dx: 2, dy: 0, weight: 4.0 / 42.0 },
+    DiffusionStep { dx: -2, dy: 1, weight: 2.0 / 42.0 },
+    DiffusionStep { dx: -1, dy: 1, weight: 4.0 / 42.0 },
+    DiffusionStep { dx: 0, dy: 1, weight: 8.0 / 42.0 },
+    DiffusionStep { dx: 1, dy: 1, weight: 4.0 / 42.0 },
+    DiffusionStep { dx: 2, dy: 1, weight: 2.0 / 42.0 },
+    DiffusionStep { dx: -2, dy: 2, weight: 1.0 / 42.0 },
+    DiffusionStep { dx: -1, dy: 2, weight: 2.0 / 42.0 },
+    DiffusionStep { dx: 0, dy: 2, weight: 4.0 / 42.0 },
+    DiffusionStep { dx: 1, dy: 2, weight: 2.0 / 42.0 },
+    DiffusionStep { dx: 2, dy: 2, weight: 1.0 / 42.0 },
+];
+
+const ATKINSON: [DiffusionStep; 6] = [
+    DiffusionStep { dx: 1, dy: 0, weight: 1.0 / 8.0 },
+    DiffusionStep { dx: 2, dy: 0, weight: 1.0 / 8.0 },
+    DiffusionStep { dx: -1, dy: 1, weight: 1.0 / 8.0 },
+    DiffusionStep { dx: 0, dy: 1, weight: 1.0 / 8.0 },
+    DiffusionStep { dx: 1, dy: 1, weight: 1.0 / 8.0 },
+    DiffusionStep { dx: 0, dy: 2, weight: 1.0 / 8.0 },
+];
+
+/// Standard 4x4 Bayer threshold matrix, row-major.
+const BAYER_4X4: [u8; 16] = [
+    0, 8, 2, 10, //
+    12, 4, 14, 6, //
+    3, 11, 1, 9, //
+    15, 7, 13, 5,
+];
+
+/// Standard 8x8 Bayer threshold matrix, row-major.
+const BAYER_8X8: [u8; 64] = [
+    0, 48, 12, 60, 3, 51, 15, 63, //
+    32, 16, 44, 28, 35, 19, 47, 31, //
+    8, 56, 4, 52, 11, 59, 7, 55, //
+    40, 24, 36, 20, 43, 27, 39, 23, //
+    2, 50, 14, 62, 1, 49, 13, 61, //
+    34, 18, 46, 30, 33, 17, 45, 29, //
+    10, 58, 6, 54, 9, 57, 5, 53, //
+    42, 26, 38, 22, 41, 25, 37, 21,
+];
+
 pub struct DitheredQR {
     big_size: usize,
+    qr_size: usize,
+    ratio: usize,
     cells: Array2<Cell>,
     targets: Array2<f32>,
     gamma: f32,
     contrast: f32,
     brightness: f32,
+    data_error_damping: f32,
+    dark_color: Rgb<u8>,
+    light_color: Rgb<u8>,
+    quiet_zone_modules: usize,
+    dither_kernel: DitherKernel,
 }
 
 impl DitheredQR {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         qr_data: &[Vec<bool>],
         ratio: usize,
         gamma: f32,
         contrast: f32,
         brightness: f32,
+        data_error_damping: f32,
+        dark_color: Rgb<u8>,
+        light_color: Rgb<u8>,
+        quiet_zone_modules: usize,
+        dither_kernel: DitherKernel,
     ) -> Result<Self> {
         let qr_size = qr_data.len();
         let big_size = qr_size * ratio;
         let center_offset = ratio / 2;
+        let alignment_centers = Self::alignment_pattern_centers(qr_size);
 
         // Build every big-grid cell independently in parallel, in row-major order.
         let flat_cells: Vec<Cell> = (0..big_size * big_size)
@@ -39,7 +147,8 @@ impl DitheredQR {
                 let sub_x = x % ratio;
 
                 let is_black = qr_data[qr_y][qr_x];
-                let is_locked = Self::is_locked_position(qr_x, qr_y, qr_size);
+                let is_locked =
+                    Self::is_locked_position(qr_x, qr_y, qr_size, &alignment_centers);
 
                 let cell_type = if is_locked {
                     CellType::Locked
@@ -62,15 +171,77 @@ impl DitheredQR {
 
         Ok(Self {
             big_size,
+            qr_size,
+            ratio,
             cells,
             targets,
             gamma,
             contrast,
             brightness,
+            data_error_damping,
+            dark_color,
+            light_color,
+            quiet_zone_modules,
+            dither_kernel,
         })
     }
 
-    fn is_locked_position(x: usize, y: usize, size: usize) -> bool {
+    /// Row/column coordinates, in ascending order, at which alignment
+    /// pattern centers may fall for a QR code of the given size, per the
+    /// standard placement algorithm (ISO/IEC 18004 Annex E). Empty for
+    /// version 1, which has no alignment patterns.
+    fn alignment_pattern_coords(qr_size: usize) -> Vec<usize> {
+        let version = (qr_size - 17) / 4;
+        if version == 1 {
+            return Vec::new();
+        }
+
+        let num_align = version / 7 + 2;
+        let step = if version == 32 {
+            26
+        } else {
+            let denom = num_align * 2 - 2;
+            let numerator = qr_size - 13;
+            // Ceiling division, then back to a step between modules.
+            numerator.div_ceil(denom) * 2
+        };
+
+        let mut coords = vec![0usize; num_align];
+        coords[0] = 6;
+        for i in 1..num_align {
+            coords[i] = (qr_size - 7) - (num_align - 1 - i) * step;
+        }
+        coords
+    }
+
+    /// Centers of every 5x5 alignment pattern for a QR code of the given
+    /// size, excluding the three positions that collide with the finder
+    /// patterns.
+    fn alignment_pattern_centers(qr_size: usize) -> Vec<(usize, usize)> {
+        let coords = Self::alignment_pattern_coords(qr_size);
+        let Some(&first) = coords.first() else {
+            return Vec::new();
+        };
+        let last = coords[coords.len() - 1];
+        let finder_collisions = [(first, first), (first, last), (last, first)];
+
+        coords
+            .iter()
+            .flat_map(|&r| coords.iter().map(move |&c| (r, c)))
+            .filter(|pos| !finder_collisions.contains(pos))
+            .collect()
+    }
+
+    /// Whether `(x, y)` (in QR module coordinates) is part of a function
+    /// pattern: finders, separators, timing patterns, version-information
+    /// blocks (version >= 7), or an alignment pattern. These modules are
+    /// fixed by the QR structure and must never be dithered.
+    fn is_locked_position(
+        x: usize,
+        y: usize,
+        size: usize,
+        alignment_centers: &[(usize, usize)],
+    ) -> bool {
         // Timing patterns
         if x == 6 || y == 6 {
             return true;
@@ -91,12 +262,21 @@ impl DitheredQR {
             return true;
         }
 
-        // Alignment pattern (bottom-right)
-        if size >= 25 && x > size - 10 && y > size - 10 && x < size - 4 && y < size - 4 {
-            return true;
+        // Version information blocks (version >= 7 only)
+        let version = (size - 17) / 4;
+        if version >= 7 {
+            if (size - 11..=size - 9).contains(&x) && y < 6 {
+                return true;
+            }
+            if (size - 11..=size - 9).contains(&y) && x < 6 {
+                return true;
+            }
         }
 
-        false
+        // Alignment patterns (5x5, centered on each computed coordinate pair)
+        alignment_centers
+            .iter()
+            .any(|&(r, c)| x.abs_diff(c) <= 2 && y.abs_diff(r) <= 2)
     }
 
     pub fn process_image(&mut self, img: &RgbImage) -> Result<()> {
@@ -141,7 +321,7 @@ impl DitheredQR {
 
                 let target = self.targets[[y, x]];
                 let actual = if cell.is_black { 0.0 } else { 1.0 };
-                let error = actual - target;
+                let error = (actual - target) * self.data_error_damping;
 
                 // Symmetric 8-neighbor error diffusion for data cells
                 self.bump_target(x as i32 + 1, y as i32, error * 3.0 / 16.0);
@@ -155,7 +335,26 @@ impl DitheredQR {
             }
         }
 
-        // Second pass: process free cells (non-center cells in unlocked areas) with Floyd-Steinberg
+        // Second pass: process free cells (non-center cells in unlocked areas)
+        // with the selected kernel.
+        match self.dither_kernel {
+            DitherKernel::FloydSteinberg => self.apply_error_diffusion(&FLOYD_STEINBERG),
+            DitherKernel::JarvisJudiceNinke => self.apply_error_diffusion(&JARVIS_JUDICE_NINKE),
+            DitherKernel::Stucki => self.apply_error_diffusion(&STUCKI),
+            DitherKernel::Atkinson => self.apply_error_diffusion(&ATKINSON),
+            DitherKernel::Bayer4 => self.apply_ordered_dithering(&BAYER_4X4, 4),
+            DitherKernel::Bayer8 => self.apply_ordered_dithering(&BAYER_8X8, 8),
+        }
+    }
+
+    /// Diffuse each free cell's quantization error to its neighbors per
+    /// `offsets`. Weights on neighbors that are not `Free` (locked, `Data`
+    /// or out-of-bounds) are dropped and the remaining weights renormalized
+    /// so the same total fraction of error (`offsets` weights sum -- 1.0 for
+    /// most kernels, 0.75 for Atkinson's deliberate loss) is diffused,
+    /// regardless of how close the cell is to a function pattern. A cell
+    /// with no `Free` neighbor in the kernel diffuses no error.
+    fn apply_error_diffusion(&mut self, offsets: &'static [DiffusionStep]) {
         for y in 0..self.big_size {
             for x in 0..self.big_size {
                 let mut cell = self.cells[[y, x]];
@@ -174,35 +373,61 @@ impl DitheredQR {
                 let actual = if new_is_black { 0.0 } else { 1.0 };
                 let error = actual - target;
 
-                // Floyd-Steinberg error diffusion with dynamic weighting
-                let a = self.is_free(x as i32 + 1, y as i32);
-                let b = self.is_free(x as i32 - 1, y as i32 + 1);
-                let c = self.is_free(x as i32, y as i32 + 1);
-                let d = self.is_free(x as i32 + 1, y as i32 + 1);
-
-                let total = (if a { 7.0 } else { 0.0 })
-                    + (if b { 3.0 } else { 0.0 })
-                    + (if c { 5.0 } else { 0.0 })
-                    + (if d { 1.0 } else { 0.0 });
-
-                if total > 0.0 {
-                    if a {
-                        self.bump_target(x as i32 + 1, y as i32, error * 7.0 / total);
-                    }
-                    if b {
-                        self.bump_target(x as i32 - 1, y as i32 + 1, error * 3.0 / total);
-                    }
-                    if c {
-                        self.bump_target(x as i32, y as i32 + 1, error * 5.0 / total);
-                    }
-                    if d {
-                        self.bump_target(x as i32 + 1, y as i32 + 1, error * 1.0 / total);
+                let ideal_total: f32 = offsets.iter().map(|step| step.weight).sum();
+                let available_total: f32 = offsets
+                    .iter()
+                    .filter(|step| self.is_free(x as i32 + step.dx, y as i32 + step.dy))
+                    .map(|step| step.weight)
+                    .sum();
+
+                if available_total > 0.0 {
+                    let scale = ideal_total / available_total;
+                    for step in offsets
+                        .iter()
+                        .filter(|step| self.is_free(x as i32 + step.dx, y as i32 + step.dy))
+                    {
+                        self.bump_target(
+                            x as i32 + step.dx,
+                            y as i32 + step.dy,
+                            error * step.weight * scale,
+                        );
                     }
                 }
             }
         }
     }
 
+    /// Threshold each free cell against a tiled Bayer matrix instead of
+    /// diffusing error. Cells have no dependency on each other, so this pass
+    /// runs in parallel.
+    fn apply_ordered_dithering(&mut self, matrix: &'static [u8], matrix_size: usize) {
+        let big_size = self.big_size;
+        let cells = &self.cells;
+        let targets = &self.targets;
+
+        let updates: Vec<(usize, usize, bool)> = (0..big_size * big_size)
+            .into_par_iter()
+            .filter_map(|i| {
+                let y = i / big_size;
+                let x = i % big_size;
+
+                if cells[[y, x]].cell_type != CellType::Free {
+                    return None;
+                }
+
+                let threshold = (matrix[(y % matrix_size) * matrix_size + x % matrix_size] as f32
+                    + 0.5)
+                    / (matrix_size * matrix_size) as f32;
+
+                Some((x, y, targets[[y, x]] < threshold))
+            })
+            .collect();
+
+        for (x, y, is_black) in updates {
+            self.cells[[y, x]].is_black = is_black;
+        }
+    }
+
     fn bump_target(&mut self, x: i32, y: i32, error: f32) {
         if x >= 0 && y >= 0 && (x as usize) < self.big_size && (y as usize) < self.big_size {
             self.targets[[y as usize, x as usize]] -= error;
@@ -218,14 +443,116 @@ impl DitheredQR {
         self.cells[[y as usize, x as usize]].cell_type == CellType::Free
     }
 
+    /// Downsample the rendered grid back to one sample per QR module by
+    /// majority vote over the module's full `ratio x ratio` block of cells,
+    /// the way a scanner's own downsampling would, and count mismatches
+    /// against the original QR matrix. A cheap stand-in for a full decode
+    /// attempt. Sampling only the `Data` cell at the module's center would
+    /// miss dithering corruption entirely, since `Data` cells are fixed at
+    /// construction and only `Free` cells are mutated by `apply_dithering`.
+    pub fn sampled_module_mismatches(&self, qr_data: &[Vec<bool>]) -> usize {
+        let mut mismatches = 0;
+        for qr_y in 0..self.qr_size {
+            for qr_x in 0..self.qr_size {
+                let mut black_count = 0usize;
+                for sub_y in 0..self.ratio {
+                    for sub_x in 0..self.ratio {
+                        let y = qr_y * self.ratio + sub_y;
+                        let x = qr_x * self.ratio + sub_x;
+                        if self.cells[[y, x]].is_black {
+                            black_count += 1;
+                        }
+                    }
+                }
+
+                let sampled_black = black_count * 2 >= self.ratio * self.ratio;
+                if sampled_black != qr_data[qr_y][qr_x] {
+                    mismatches += 1;
+                }
+            }
+        }
+        mismatches
+    }
+
+    /// Pixel margin added on each side by the quiet zone.
+    fn quiet_zone_margin(&self) -> usize {
+        self.quiet_zone_modules * self.ratio
+    }
+
+    /// The rendered cell grid as black/white flags, row-major, including the
+    /// quiet zone -- the same data `render_to_image` rasterizes, useful for
+    /// vector output backends.
+    pub fn cell_grid(&self) -> Vec<Vec<bool>> {
+        let margin = self.quiet_zone_margin();
+        let out_size = self.big_size + margin * 2;
+
+        (0..out_size)
+            .map(|y| {
+                (0..out_size)
+                    .map(|x| {
+                        if x < margin
+                            || y < margin
+                            || x >= margin + self.big_size
+                            || y >= margin + self.big_size
+                        {
+                            false
+                        } else {
+                            self.cells[[y - margin, x - margin]].is_black
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
     pub fn render_to_image(&self) -> RgbImage {
-        ImageBuffer::from_fn(self.big_size as u32, self.big_size as u32, |x, y| {
-            let cell = self.cells[[y as usize, x as usize]];
+        let margin = self.quiet_zone_margin();
+        let out_size = self.big_size + margin * 2;
+
+        ImageBuffer::from_fn(out_size as u32, out_size as u32, |x, y| {
+            let x = x as usize;
+            let y = y as usize;
+
+            if x < margin || y < margin || x >= margin + self.big_size || y >= margin + self.big_size
+            {
+                return self.light_color;
+            }
+
+            let cell = self.cells[[y - margin, x - margin]];
             if cell.is_black {
-                Rgb([0, 0, 0])
+                self.dark_color
             } else {
-                Rgb([255, 255, 255])
+                self.light_color
             }
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn qr_size_for_version(version: usize) -> usize {
+        17 + version * 4
+    }
+
+    #[test]
+    fn alignment_pattern_coords_known_versions() {
+        assert_eq!(
+            DitheredQR::alignment_pattern_coords(qr_size_for_version(2)),
+            vec![6, 18]
+        );
+        assert_eq!(
+            DitheredQR::alignment_pattern_coords(qr_size_for_version(7)),
+            vec![6, 22, 38]
+        );
+        assert_eq!(
+            DitheredQR::alignment_pattern_coords(qr_size_for_version(14)),
+            vec![6, 26, 46, 66]
+        );
+        assert_eq!(
+            DitheredQR::alignment_pattern_coords(qr_size_for_version(32)),
+            vec![6, 34, 60, 86, 112, 138]
+        );
+    }
+}
@@ -0,0 +1,158 @@
+//! Core dithering engine for overlaying images onto QR codes, independent of
+//! the CLI. Other Rust programs (servers, GUIs, WASM bindings) can depend on
+//! this crate directly instead of shelling out to the binary.
+
+pub mod color;
+pub mod dither_qr;
+pub mod qr;
+pub mod svg;
+pub mod verify;
+
+pub use dither_qr::{DitherKernel, DitheredQR};
+pub use qr::{generate_qr_data, Cell, CellType};
+
+use anyhow::Result;
+use image::{Rgb, RgbImage};
+use qrcode::EcLevel;
+
+/// Tunable parameters for [`generate`], mirroring the CLI's flags.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub ratio: usize,
+    pub gamma: f32,
+    pub contrast: f32,
+    pub brightness: f32,
+    pub ec_level: EcLevel,
+    pub upscale: u32,
+    pub dark_color: Rgb<u8>,
+    pub light_color: Rgb<u8>,
+    pub quiet_zone_modules: usize,
+    pub dither_kernel: DitherKernel,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            ratio: 3,
+            gamma: 2.2,
+            contrast: 1.0,
+            brightness: 0.0,
+            ec_level: EcLevel::L,
+            upscale: 1,
+            dark_color: Rgb([0, 0, 0]),
+            light_color: Rgb([255, 255, 255]),
+            quiet_zone_modules: 4,
+            dither_kernel: DitherKernel::FloydSteinberg,
+        }
+    }
+}
+
+/// Run the dithering pipeline end to end: encode `text` as a QR code, overlay
+/// `image` via the configured dithering kernel, and return the finished image.
+pub fn generate(text: &str, image: &RgbImage, config: &Config) -> Result<RgbImage> {
+    if config.ratio % 2 == 0 {
+        return Err(anyhow::anyhow!("Ratio must be odd"));
+    }
+
+    let qr_data = qr::generate_qr_data(text, config.ec_level)?;
+    let mut dithered_qr = DitheredQR::new(
+        &qr_data,
+        config.ratio,
+        config.gamma,
+        config.contrast,
+        config.brightness,
+        1.0,
+        config.dark_color,
+        config.light_color,
+        config.quiet_zone_modules,
+        config.dither_kernel,
+    )?;
+
+    dithered_qr.process_image(image)?;
+    dithered_qr.apply_dithering();
+
+    let mut output_img = dithered_qr.render_to_image();
+    if config.upscale > 1 {
+        output_img = image::imageops::resize(
+            &output_img,
+            output_img.width() * config.upscale,
+            output_img.height() * config.upscale,
+            image::imageops::FilterType::Nearest,
+        );
+    }
+
+    Ok(output_img)
+}
+
+/// Builder over [`generate`], mirroring the ergonomics of
+/// `qrcode::QrCode::render()`: construct with [`Builder::new`], tune fields,
+/// then call [`Builder::build`] to run the pipeline.
+pub struct Builder<'a> {
+    text: &'a str,
+    image: &'a RgbImage,
+    config: Config,
+}
+
+impl<'a> Builder<'a> {
+    pub fn new(text: &'a str, image: &'a RgbImage) -> Self {
+        Self {
+            text,
+            image,
+            config: Config::default(),
+        }
+    }
+
+    pub fn ratio(mut self, ratio: usize) -> Self {
+        self.config.ratio = ratio;
+        self
+    }
+
+    pub fn gamma(mut self, gamma: f32) -> Self {
+        self.config.gamma = gamma;
+        self
+    }
+
+    pub fn contrast(mut self, contrast: f32) -> Self {
+        self.config.contrast = contrast;
+        self
+    }
+
+    pub fn brightness(mut self, brightness: f32) -> Self {
+        self.config.brightness = brightness;
+        self
+    }
+
+    pub fn ec_level(mut self, ec_level: EcLevel) -> Self {
+        self.config.ec_level = ec_level;
+        self
+    }
+
+    pub fn upscale(mut self, upscale: u32) -> Self {
+        self.config.upscale = upscale;
+        self
+    }
+
+    pub fn dark_color(mut self, dark_color: Rgb<u8>) -> Self {
+        self.config.dark_color = dark_color;
+        self
+    }
+
+    pub fn light_color(mut self, light_color: Rgb<u8>) -> Self {
+        self.config.light_color = light_color;
+        self
+    }
+
+    pub fn quiet_zone_modules(mut self, quiet_zone_modules: usize) -> Self {
+        self.config.quiet_zone_modules = quiet_zone_modules;
+        self
+    }
+
+    pub fn dither_kernel(mut self, dither_kernel: DitherKernel) -> Self {
+        self.config.dither_kernel = dither_kernel;
+        self
+    }
+
+    pub fn build(self) -> Result<RgbImage> {
+        generate(self.text, self.image, &self.config)
+    }
+}
@@ -1,13 +1,91 @@
 mod args;
-mod dither_qr;
-mod qr;
 
 use anyhow::Result;
-use args::Args;
+use args::{Args, EcArg, FormatArg};
 use clap::Parser;
-use dither_qr::DitheredQR;
-use image::imageops;
-use qr::generate_qr_data;
+use dither_qr::{color, generate_qr_data, svg, verify, DitherKernel, DitheredQR};
+use image::{imageops, Rgb, RgbImage};
+
+/// EC levels in escalation order, weakest to strongest.
+const EC_ESCALATION: [EcArg; 4] = [EcArg::L, EcArg::M, EcArg::Q, EcArg::H];
+/// Upper bound on how far the ratio is allowed to grow while retuning.
+const MAX_RATIO: usize = 11;
+
+/// The product of the dithering pipeline: both the rasterized image and the
+/// underlying cell grid, so callers can pick either a raster or vector
+/// output without re-running the pipeline.
+struct Rendered {
+    output_img: RgbImage,
+    cell_grid: Vec<Vec<bool>>,
+}
+
+struct Attempt {
+    rendered: Rendered,
+    qr_data: Vec<Vec<bool>>,
+    mismatches: usize,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn render_attempt(
+    text: &str,
+    img: &RgbImage,
+    ec_level: EcArg,
+    ratio: usize,
+    gamma: f32,
+    contrast: f32,
+    brightness: f32,
+    data_error_damping: f32,
+    dark_color: Rgb<u8>,
+    light_color: Rgb<u8>,
+    quiet_zone: usize,
+    dither_kernel: DitherKernel,
+) -> Result<Attempt> {
+    let qr_data = generate_qr_data(text, ec_level.into())?;
+    let mut dithered_qr = DitheredQR::new(
+        &qr_data,
+        ratio,
+        gamma,
+        contrast,
+        brightness,
+        data_error_damping,
+        dark_color,
+        light_color,
+        quiet_zone,
+        dither_kernel,
+    )?;
+
+    dithered_qr.process_image(img)?;
+    dithered_qr.apply_dithering();
+
+    let output_img = dithered_qr.render_to_image();
+    let cell_grid = dithered_qr.cell_grid();
+    let mismatches = dithered_qr.sampled_module_mismatches(&qr_data);
+
+    Ok(Attempt {
+        rendered: Rendered {
+            output_img,
+            cell_grid,
+        },
+        qr_data,
+        mismatches,
+    })
+}
+
+fn to_hex(c: Rgb<u8>) -> String {
+    format!("#{:02x}{:02x}{:02x}", c.0[0], c.0[1], c.0[2])
+}
+
+fn is_svg_output(args: &Args) -> bool {
+    match args.format {
+        FormatArg::Svg => true,
+        FormatArg::Raster => false,
+        FormatArg::Auto => args
+            .output
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("svg")),
+    }
+}
 
 fn main() -> Result<()> {
     let args = Args::parse();
@@ -16,36 +94,128 @@ fn main() -> Result<()> {
         return Err(anyhow::anyhow!("Ratio must be odd"));
     }
 
+    let contrast_ratio = color::contrast_ratio(args.dark_color, args.light_color);
+    if contrast_ratio < 4.5 {
+        eprintln!(
+            "Warning: dark/light contrast ratio is only {:.1}:1 (recommend at least 4.5:1); scans may be unreliable",
+            contrast_ratio
+        );
+    }
+
     println!("Generating QR code for: {}", args.text);
-    let qr_data = generate_qr_data(&args.text, args.error_correction.into())?;
 
     println!("Loading image: {}", args.image.display());
     let img = image::open(&args.image)?.to_rgb8();
 
-    let mut dithered_qr = DitheredQR::new(
-        &qr_data,
-        args.ratio,
-        args.gamma,
-        args.contrast,
-        args.brightness,
-    )?;
+    let rendered = if args.verify {
+        let mut ec_idx = EC_ESCALATION
+            .iter()
+            .position(|ec| *ec == args.error_correction)
+            .unwrap_or(0);
+        let mut ratio = args.ratio;
+        let mut damping = 1.0f32;
+        let mut chosen = None;
 
-    dithered_qr.process_image(&img)?;
-    dithered_qr.apply_dithering();
+        for attempt in 0..=args.verify_retries {
+            let ec_level = EC_ESCALATION[ec_idx];
+            let result = render_attempt(
+                &args.text,
+                &img,
+                ec_level,
+                ratio,
+                args.gamma,
+                args.contrast,
+                args.brightness,
+                damping,
+                args.dark_color,
+                args.light_color,
+                args.quiet_zone,
+                args.dither.into(),
+            )?;
+
+            let budget = verify::ec_capacity_modules(result.qr_data.len(), ec_level.into());
+            let scannable = result.mismatches <= budget
+                && verify::decode_matches(&result.rendered.output_img, &args.text);
+
+            if scannable {
+                println!(
+                    "Verified scannable on attempt {} (ec={:?}, ratio={}, damping={:.2})",
+                    attempt + 1,
+                    ec_level,
+                    ratio,
+                    damping
+                );
+                chosen = Some(result.rendered);
+                break;
+            }
+
+            println!(
+                "Attempt {} not reliably scannable ({} mismatched modules, budget {}); retuning",
+                attempt + 1,
+                result.mismatches,
+                budget
+            );
 
-    let mut output_img = dithered_qr.render_to_image();
+            if ec_idx + 1 < EC_ESCALATION.len() {
+                ec_idx += 1;
+            } else if ratio + 2 <= MAX_RATIO {
+                ratio += 2;
+            } else {
+                damping *= 0.7;
+            }
+        }
 
-    if args.upscale > 1 {
-        output_img = imageops::resize(
-            &output_img,
-            output_img.width() * args.upscale,
-            output_img.height() * args.upscale,
-            imageops::FilterType::Nearest,
+        chosen.ok_or_else(|| {
+            anyhow::anyhow!(
+                "Failed to produce a scannable QR code within {} attempts",
+                args.verify_retries + 1
+            )
+        })?
+    } else {
+        let qr_data = generate_qr_data(&args.text, args.error_correction.into())?;
+        let mut dithered_qr = DitheredQR::new(
+            &qr_data,
+            args.ratio,
+            args.gamma,
+            args.contrast,
+            args.brightness,
+            1.0,
+            args.dark_color,
+            args.light_color,
+            args.quiet_zone,
+            args.dither.into(),
+        )?;
+
+        dithered_qr.process_image(&img)?;
+        dithered_qr.apply_dithering();
+
+        Rendered {
+            output_img: dithered_qr.render_to_image(),
+            cell_grid: dithered_qr.cell_grid(),
+        }
+    };
+
+    if is_svg_output(&args) {
+        let document = svg::render_svg(
+            &rendered.cell_grid,
+            args.svg_scale,
+            &to_hex(args.dark_color),
+            &to_hex(args.light_color),
         );
+        std::fs::write(&args.output, document)?;
+    } else {
+        let mut output_img = rendered.output_img;
+        if args.upscale > 1 {
+            output_img = imageops::resize(
+                &output_img,
+                output_img.width() * args.upscale,
+                output_img.height() * args.upscale,
+                imageops::FilterType::Nearest,
+            );
+        }
+        output_img.save(&args.output)?;
     }
 
-    output_img.save(&args.output)?;
-
     println!("Saved to: {}", args.output.display());
     Ok(())
 }
@@ -0,0 +1,49 @@
+use std::fmt::Write as _;
+
+/// Render a row-major black/white cell grid to an SVG document.
+///
+/// Each cell is `module_scale` user units square. Horizontal runs of
+/// black cells within a row are merged into a single `<rect>` to keep the
+/// document small.
+pub fn render_svg(cells: &[Vec<bool>], module_scale: f32, dark_color: &str, light_color: &str) -> String {
+    let size = cells.len();
+    let side = size as f32 * module_scale;
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{side}" height="{side}" viewBox="0 0 {side} {side}">"#,
+    );
+    let _ = writeln!(
+        svg,
+        r#"<rect x="0" y="0" width="{side}" height="{side}" fill="{light_color}"/>"#
+    );
+
+    for (y, row) in cells.iter().enumerate() {
+        let mut x = 0;
+        while x < size {
+            if !row[x] {
+                x += 1;
+                continue;
+            }
+
+            let run_start = x;
+            while x < size && row[x] {
+                x += 1;
+            }
+            let run_len = x - run_start;
+
+            let _ = writeln!(
+                svg,
+                r#"<rect x="{x}" y="{y}" width="{w}" height="{h}" fill="{dark_color}"/>"#,
+                x = run_start as f32 * module_scale,
+                y = y as f32 * module_scale,
+                w = run_len as f32 * module_scale,
+                h = module_scale,
+            );
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
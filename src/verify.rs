@@ -0,0 +1,33 @@
+use image::RgbImage;
+use qrcode::EcLevel;
+
+/// Approximate fraction of a QR code's modules that a given error-correction
+/// level can still recover from, per the standard EC capability percentages.
+fn ec_capacity_fraction(ec_level: EcLevel) -> f32 {
+    match ec_level {
+        EcLevel::L => 0.07,
+        EcLevel::M => 0.15,
+        EcLevel::Q => 0.25,
+        EcLevel::H => 0.30,
+    }
+}
+
+/// Rough error-correction budget, in modules, for a QR code of the given size
+/// and EC level. Used as a cheap pre-check before invoking a full decoder:
+/// if the number of mismatched modules exceeds this, there's no point
+/// attempting a decode.
+pub fn ec_capacity_modules(qr_size: usize, ec_level: EcLevel) -> usize {
+    let total_modules = qr_size * qr_size;
+    (total_modules as f32 * ec_capacity_fraction(ec_level)) as usize
+}
+
+/// Decode `img` with a pure-Rust QR decoder and check it reproduces `expected`.
+pub fn decode_matches(img: &RgbImage, expected: &str) -> bool {
+    let luma = image::imageops::grayscale(img);
+    let mut prepared = rqrr::PreparedImage::prepare(luma);
+    prepared.detect_grids().iter().any(|grid| {
+        grid.decode()
+            .map(|(_, content)| content == expected)
+            .unwrap_or(false)
+    })
+}